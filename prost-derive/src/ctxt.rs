@@ -0,0 +1,77 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+
+use quote::ToTokens;
+
+/// A context for accumulating errors while parsing `#[prost(..)]` attributes
+/// and the shapes they annotate.
+///
+/// Every error discovered while parsing the fields or variants of a single
+/// `#[derive(..)]` invocation is pushed onto the same `Ctxt`, rather than
+/// aborting immediately, so that a single expansion can report every mistake
+/// it finds instead of only the first one. Checks on the input as a whole
+/// (e.g. deriving `Message` for an enum or a generic type) return their error
+/// at once and do not go through a `Ctxt`. Once parsing finishes, `check` folds the accumulated errors
+/// into a single `syn::Error` (via `syn::Error::combine`) suitable for
+/// returning as a compile error.
+///
+/// This mirrors the `Ctxt` used internally by `serde_derive`.
+pub struct Ctxt {
+    // `RefCell` rather than `Cell` so that we can push onto the `Vec`.
+    //
+    // `Option` is used so that `check` can take the errors out of the
+    // `RefCell` without replacing them with an empty `Vec`, which would
+    // squelch the "forgot to call check" detection in `Drop`.
+    errors: RefCell<Option<Vec<syn::Error>>>,
+}
+
+impl Ctxt {
+    /// Creates a new context for accumulating errors.
+    pub fn new() -> Self {
+        Ctxt {
+            errors: RefCell::new(Some(Vec::new())),
+        }
+    }
+
+    /// Reports an error with the given `message` spanned by the tokens in
+    /// `obj`, e.g. the field or attribute that the error pertains to.
+    pub fn error_spanned_by<A: ToTokens, T: Display>(&self, obj: A, message: T) {
+        self.errors
+            .borrow_mut()
+            .as_mut()
+            .unwrap()
+            .push(syn::Error::new_spanned(obj.into_token_stream(), message));
+    }
+
+    /// Consumes the context, returning `Ok` if no errors were accumulated,
+    /// or an `Err` combining every accumulated error otherwise.
+    ///
+    /// Must be called before the `Ctxt` is dropped, or the `Drop` impl
+    /// panics to guard against silently swallowed errors.
+    pub fn check(self) -> Result<(), syn::Error> {
+        let errors = self.errors.borrow_mut().take().unwrap();
+        let mut iter = errors.into_iter();
+        let mut combined = match iter.next() {
+            Some(error) => error,
+            None => return Ok(()),
+        };
+        for error in iter {
+            combined.combine(error);
+        }
+        Err(combined)
+    }
+}
+
+impl Default for Ctxt {
+    fn default() -> Self {
+        Ctxt::new()
+    }
+}
+
+impl Drop for Ctxt {
+    fn drop(&mut self) {
+        if !std::thread::panicking() && self.errors.borrow().is_some() {
+            panic!("forgot to call Ctxt::check");
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::fmt;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{Attribute, Ident, Lit, Meta, MetaList, MetaNameValue, NestedMeta};
+
+use crate::ctxt::Ctxt;
+
+/// Scalar protobuf field types.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ty {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+    Enumeration(syn::Path),
+}
+
+impl Ty {
+    fn from_attr(s: &str) -> Option<Ty> {
+        let ty = match s {
+            "double" => Ty::Double,
+            "float" => Ty::Float,
+            "int32" => Ty::Int32,
+            "int64" => Ty::Int64,
+            "uint32" => Ty::Uint32,
+            "uint64" => Ty::Uint64,
+            "sint32" => Ty::Sint32,
+            "sint64" => Ty::Sint64,
+            "fixed32" => Ty::Fixed32,
+            "fixed64" => Ty::Fixed64,
+            "sfixed32" => Ty::Sfixed32,
+            "sfixed64" => Ty::Sfixed64,
+            "bool" => Ty::Bool,
+            "string" => Ty::String,
+            "bytes" => Ty::Bytes,
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    /// The Rust type used to store a value of this protobuf type.
+    fn rust_type(&self) -> TokenStream {
+        match self {
+            Ty::Double => quote!(f64),
+            Ty::Float => quote!(f32),
+            Ty::Int32 | Ty::Sint32 | Ty::Sfixed32 => quote!(i32),
+            Ty::Int64 | Ty::Sint64 | Ty::Sfixed64 => quote!(i64),
+            Ty::Uint32 | Ty::Fixed32 => quote!(u32),
+            Ty::Uint64 | Ty::Fixed64 => quote!(u64),
+            Ty::Bool => quote!(bool),
+            Ty::String => quote!(::prost::alloc::string::String),
+            Ty::Bytes => quote!(::prost::alloc::vec::Vec<u8>),
+            Ty::Enumeration(..) => quote!(i32),
+        }
+    }
+
+    /// The `prost::encoding` module that implements this scalar type.
+    fn module(&self) -> Ident {
+        let name = match self {
+            Ty::Double => "double",
+            Ty::Float => "float",
+            Ty::Int32 => "int32",
+            Ty::Int64 => "int64",
+            Ty::Uint32 => "uint32",
+            Ty::Uint64 => "uint64",
+            Ty::Sint32 => "sint32",
+            Ty::Sint64 => "sint64",
+            Ty::Fixed32 => "fixed32",
+            Ty::Fixed64 => "fixed64",
+            Ty::Sfixed32 => "sfixed32",
+            Ty::Sfixed64 => "sfixed64",
+            Ty::Bool => "bool",
+            Ty::String => "string",
+            Ty::Bytes => "bytes",
+            Ty::Enumeration(..) => "int32",
+        };
+        Ident::new(name, proc_macro2::Span::call_site())
+    }
+}
+
+/// Whether a field is plain, optional, or repeated.
+#[derive(Clone, PartialEq)]
+enum Label {
+    /// A plain field, written unconditionally.
+    Plain,
+    /// An `Option<T>` field.
+    Optional,
+    /// A `Vec<T>` repeated field.
+    Repeated,
+}
+
+/// How a field should be rendered in the generated `Debug` impl.
+#[derive(Clone, PartialEq)]
+enum DebugAttr {
+    /// Format the field's value as usual.
+    Normal,
+    /// Omit the field from the generated `Debug` impl entirely.
+    Skip,
+    /// Format a fixed placeholder instead of the field's real value.
+    Redact,
+}
+
+/// A single protobuf field, parsed from a `#[prost(..)]` attribute.
+#[derive(Clone)]
+pub struct Field {
+    pub ty: Ty,
+    label: Label,
+    tag: u32,
+    debug_attr: DebugAttr,
+}
+
+impl Field {
+    /// Creates a new `Field` from a field's attributes, returning `None` if
+    /// the field should be ignored (e.g. `#[prost(skip)]`) or if no type or
+    /// tag could be determined, in which case the problem has already been
+    /// reported to `ctxt`. Other invalid attributes are reported to `ctxt`,
+    /// but the field is still returned.
+    ///
+    /// `tag` provides the fallback tag number to use if none is specified
+    /// explicitly.
+    pub fn new(ctxt: &Ctxt, attrs: Vec<Attribute>, tag: Option<u32>) -> Option<Field> {
+        let mut ty = None;
+        let mut explicit_tag = None;
+        let mut label = Label::Plain;
+        let mut skip_field = false;
+        let mut debug_attr = DebugAttr::Normal;
+
+        for meta_item in prost_attrs(&attrs) {
+            match &meta_item {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    skip_field = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("optional") => {
+                    label = Label::Optional;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("repeated") => {
+                    label = Label::Repeated;
+                }
+                // Handled by the `Oneof` expander, not by `Field` itself.
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("no_from") => {}
+                NestedMeta::Meta(Meta::Path(path)) => {
+                    let name = path.get_ident().map(|i| i.to_string()).unwrap_or_default();
+                    match Ty::from_attr(&name) {
+                        Some(t) => ty = Some(t),
+                        None => ctxt.error_spanned_by(path, "unknown prost field attribute"),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Int(lit),
+                    ..
+                })) if path.is_ident("tag") => match lit.base10_parse::<u32>() {
+                    Ok(tag) => explicit_tag = Some(tag),
+                    Err(error) => ctxt.error_spanned_by(lit, error.to_string()),
+                },
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("tag") => match lit.value().parse::<u32>() {
+                    Ok(tag) => explicit_tag = Some(tag),
+                    Err(_) => ctxt.error_spanned_by(lit, "invalid tag attribute, expected an integer"),
+                },
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("enumeration") => match lit.parse::<syn::Path>() {
+                    Ok(path) => ty = Some(Ty::Enumeration(path)),
+                    Err(_) => ctxt.error_spanned_by(lit, "invalid enumeration attribute, expected a path"),
+                },
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("debug") => match lit.value().as_str() {
+                    "skip" => debug_attr = DebugAttr::Skip,
+                    "redact" => debug_attr = DebugAttr::Redact,
+                    other => ctxt.error_spanned_by(
+                        lit,
+                        format!(
+                            "unknown `debug` attribute value `{}`, expected `skip` or `redact`",
+                            other
+                        ),
+                    ),
+                },
+                NestedMeta::Meta(Meta::List(MetaList { path, .. })) if path.is_ident("enumeration") => {}
+                _ => ctxt.error_spanned_by(meta_item, "unknown prost field attribute"),
+            }
+        }
+
+        if skip_field {
+            return None;
+        }
+
+        let ty = match ty {
+            Some(ty) => ty,
+            None => {
+                ctxt.error_spanned_by(
+                    &attrs_tokens(&attrs),
+                    "field must have a type attribute, e.g. #[prost(int32, tag = \"1\")]",
+                );
+                return None;
+            }
+        };
+
+        let tag = match explicit_tag.or(tag) {
+            Some(tag) => tag,
+            None => {
+                ctxt.error_spanned_by(&attrs_tokens(&attrs), "field is missing a tag attribute");
+                return None;
+            }
+        };
+
+        Some(Field {
+            ty,
+            label,
+            tag,
+            debug_attr,
+        })
+    }
+
+    /// The tags occupied by this field (always exactly one, for scalar fields).
+    pub fn tags(&self) -> Vec<u32> {
+        vec![self.tag]
+    }
+
+    fn module(&self) -> Ident {
+        self.ty.module()
+    }
+
+    pub fn encode(&self, ident: TokenStream) -> TokenStream {
+        let tag = self.tag;
+        let module = self.module();
+        match self.label {
+            Label::Plain => quote!(::prost::encoding::#module::encode(#tag, &#ident, buf)),
+            Label::Optional => quote! {
+                if let ::std::option::Option::Some(ref value) = #ident {
+                    ::prost::encoding::#module::encode(#tag, value, buf);
+                }
+            },
+            Label::Repeated => quote!(::prost::encoding::#module::encode_repeated(#tag, &#ident, buf)),
+        }
+    }
+
+    pub fn merge(&self, ident: TokenStream) -> TokenStream {
+        let module = self.module();
+        match self.label {
+            Label::Plain => quote!(::prost::encoding::#module::merge(wire_type, #ident, buf, ctx)),
+            Label::Optional => quote! {
+                ::prost::encoding::#module::merge(
+                    wire_type,
+                    #ident.get_or_insert_with(::std::default::Default::default),
+                    buf,
+                    ctx,
+                )
+            },
+            Label::Repeated => quote!(::prost::encoding::#module::merge_repeated(wire_type, #ident, buf, ctx)),
+        }
+    }
+
+    pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
+        let tag = self.tag;
+        let module = self.module();
+        match self.label {
+            Label::Plain => quote!(::prost::encoding::#module::encoded_len(#tag, &#ident)),
+            Label::Optional => quote! {
+                #ident.as_ref().map_or(0, |value| ::prost::encoding::#module::encoded_len(#tag, value))
+            },
+            Label::Repeated => quote!(::prost::encoding::#module::encoded_len_repeated(#tag, &#ident)),
+        }
+    }
+
+    pub fn clear(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Plain => {
+                let default = self.default();
+                quote!(#ident = #default)
+            }
+            Label::Optional => quote!(#ident = ::std::option::Option::None),
+            Label::Repeated => quote!(#ident.clear()),
+        }
+    }
+
+    pub fn default(&self) -> TokenStream {
+        match self.label {
+            Label::Plain => quote!(::std::default::Default::default()),
+            Label::Optional => quote!(::std::option::Option::None),
+            Label::Repeated => quote!(::std::vec::Vec::new()),
+        }
+    }
+
+    pub fn methods(&self, _ident: &Ident) -> Vec<TokenStream> {
+        Vec::new()
+    }
+
+    /// The Rust type that a value of this field is stored as.
+    pub fn rust_type(&self) -> TokenStream {
+        let inner = self.ty.rust_type();
+        match self.label {
+            Label::Plain => inner,
+            Label::Optional => quote!(::std::option::Option<#inner>),
+            Label::Repeated => quote!(::prost::alloc::vec::Vec<#inner>),
+        }
+    }
+
+    pub fn debug(&self, ident: TokenStream) -> TokenStream {
+        match self.debug_attr {
+            DebugAttr::Redact => quote!(&"<redacted>"),
+            // `Skip` fields are omitted by the caller (both `try_message`
+            // and `try_oneof`) before `debug` is ever called; `Normal`
+            // falls through to the usual formatting.
+            DebugAttr::Skip | DebugAttr::Normal => quote!(&#ident),
+        }
+    }
+
+    /// Whether this field should be omitted from the generated `Debug` impl
+    /// entirely, per `#[prost(debug = "skip")]`.
+    pub fn skip_debug(&self) -> bool {
+        self.debug_attr == DebugAttr::Skip
+    }
+}
+
+impl fmt::Debug for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Field")
+            .field("ty", &self.ty)
+            .field("tag", &self.tag)
+            .finish()
+    }
+}
+
+/// Returns the `#[prost(..)]` meta items across all of a field's attributes.
+fn prost_attrs(attrs: &[Attribute]) -> Vec<NestedMeta> {
+    attrs
+        .iter()
+        .flat_map(Attribute::parse_meta)
+        .flat_map(|meta| match meta {
+            Meta::List(MetaList { path, nested, .. }) if path.is_ident("prost") => {
+                nested.into_iter().collect()
+            }
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Re-parses a field's attributes into a single token stream, for spanning
+/// errors that aren't attributable to one specific meta item (e.g. a field
+/// with no `#[prost(..)]` attribute at all).
+fn attrs_tokens(attrs: &[Attribute]) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for attr in attrs {
+        attr.to_tokens(&mut tokens);
+    }
+    tokens
+}
@@ -4,6 +4,8 @@
 
 extern crate proc_macro;
 
+use std::collections::HashMap;
+
 use anyhow::bail;
 use quote::quote;
 
@@ -12,17 +14,21 @@ use itertools::Itertools;
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 use syn::{
-    punctuated::Punctuated, Data, DataEnum, DataStruct, DeriveInput, Fields, FieldsNamed,
-    FieldsUnnamed, Ident, ImplItem, ItemImpl, Variant,
+    punctuated::Punctuated, Attribute, Data, DataEnum, DataStruct, DeriveInput, Fields,
+    FieldsNamed, FieldsUnnamed, Ident, ImplItem, ItemImpl, Meta, MetaList, NestedMeta, Type,
+    Variant,
 };
 
+mod ctxt;
 mod field;
+use crate::ctxt::Ctxt;
 use crate::field::Field;
 
 fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     let input: DeriveInput = syn::parse(input)?;
 
     let ident = input.ident;
+    let constructor = has_prost_attr(&input.attrs, "constructor");
 
     let variant_data = match input.data {
         Data::Struct(variant_data) => variant_data,
@@ -34,6 +40,11 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         bail!("Message may not be derived for generic type");
     }
 
+    // Unnamed fields fall back to a synthesized index-based identifier
+    // (e.g. `0`, `1`) below, which is not a valid Rust binder; `new(...)`
+    // needs real field names for its parameters and struct-literal init.
+    let is_tuple_struct = matches!(variant_data.fields, Fields::Unnamed(_));
+
     let fields = match variant_data {
         DataStruct {
             fields: Fields::Named(FieldsNamed { named: fields, .. }),
@@ -52,6 +63,18 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         } => Vec::new(),
     };
 
+    let ctxt = Ctxt::new();
+
+    if constructor && is_tuple_struct {
+        ctxt.error_spanned_by(
+            &ident,
+            format!(
+                "#[prost(constructor)] is not supported for tuple struct {}; use named fields",
+                ident
+            ),
+        );
+    }
+
     let mut next_tag: u32 = 1;
     let mut fields = fields
         .into_iter()
@@ -60,18 +83,15 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             let field_ident = field
                 .ident
                 .unwrap_or_else(|| Ident::new(&idx.to_string(), Span::call_site()));
-            match Field::new(field.attrs, Some(next_tag)) {
-                Ok(Some(field)) => {
+            match Field::new(&ctxt, field.attrs, Some(next_tag)) {
+                Some(field) => {
                     next_tag = field.tags().iter().max().map(|t| t + 1).unwrap_or(next_tag);
-                    Some(Ok((field_ident, field)))
+                    Some((field_ident, field))
                 }
-                Ok(None) => None,
-                Err(err) => Some(Err(
-                    err.context(format!("invalid message field {}.{}", ident, field_ident))
-                )),
+                None => None,
             }
         })
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<Vec<_>>();
 
     // We want Debug to be in declaration order
     let unsorted_fields = fields.clone();
@@ -91,9 +111,11 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     tags.sort();
     tags.dedup();
     if tags.len() != num_tags {
-        bail!("message {} has fields with duplicate tags", ident);
+        ctxt.error_spanned_by(&ident, format!("message {} has fields with duplicate tags", ident));
     }
 
+    ctxt.check()?;
+
     let encoded_len = fields
         .iter()
         .map(|&(ref field_ident, ref field)| field.encoded_len(quote!(self.#field_ident)));
@@ -140,10 +162,32 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         quote!(#field_ident: #value,)
     });
 
-    let methods = fields
+    let mut methods = fields
         .iter()
         .flat_map(|&(ref field_ident, ref field)| field.methods(field_ident))
         .collect::<Vec<_>>();
+
+    if constructor {
+        // `unsorted_fields` preserves declaration order, which is the order
+        // callers expect to supply arguments in.
+        let params = unsorted_fields.iter().map(|&(ref field_ident, ref field)| {
+            let ty = field.rust_type();
+            quote!(#field_ident: #ty)
+        });
+        let inits = unsorted_fields
+            .iter()
+            .map(|&(ref field_ident, _)| quote!(#field_ident,));
+        let doc = format!("Constructs a new `{}`, setting every field to the given value.", ident);
+        methods.push(quote! {
+            #[doc = #doc]
+            pub fn new(#(#params),*) -> Self {
+                #ident {
+                    #(#inits)*
+                }
+            }
+        });
+    }
+
     let methods = if methods.is_empty() {
         quote!()
     } else {
@@ -155,20 +199,23 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         }
     };
 
-    let debugs = unsorted_fields.iter().map(|&(ref field_ident, ref field)| {
-        let wrapper = field.debug(quote!(self.#field_ident));
-        let call = if is_struct {
-            quote!(builder.field(stringify!(#field_ident), &wrapper))
-        } else {
-            quote!(builder.field(&wrapper))
-        };
-        quote! {
-             let builder = {
-                 let wrapper = #wrapper;
-                 #call
-             };
-        }
-    });
+    let debugs = unsorted_fields
+        .iter()
+        .filter(|&&(_, ref field)| !field.skip_debug())
+        .map(|&(ref field_ident, ref field)| {
+            let wrapper = field.debug(quote!(self.#field_ident));
+            let call = if is_struct {
+                quote!(builder.field(stringify!(#field_ident), &wrapper))
+            } else {
+                quote!(builder.field(&wrapper))
+            };
+            quote! {
+                 let builder = {
+                     let wrapper = #wrapper;
+                     #call
+                 };
+            }
+        });
     let debug_builder = if is_struct {
         quote!(f.debug_struct(stringify!(#ident)))
     } else {
@@ -272,6 +319,26 @@ fn try_enumeration(_attr: TokenStream, input: TokenStream) -> Result<TokenStream
     let default = &variants[0];
     let ty = &impl_.self_ty;
 
+    // `prost` (as pinned by `html_root_url` above) has no `UnknownEnumValue`
+    // type to reuse, so the `TryFrom` error is a dedicated type generated
+    // alongside the enum itself, named after it to avoid clashing with any
+    // other `#[enumeration]`-derived type in the same module.
+    //
+    // Note that there is deliberately no generated `From<i32>`: std's
+    // blanket `impl<T, U: Into<T>> TryFrom<U> for T` means a `From<i32>`
+    // impl here would conflict with the `TryFrom<i32>` impl below
+    // (E0119), so the infallible conversion is dropped in favor of the
+    // strict one.
+    let ty_ident = match &**ty {
+        Type::Path(type_path) => &type_path.path.segments.last().unwrap().ident,
+        _ => bail!("enumeration may only be applied to impls for a named type"),
+    };
+    let error_ident = Ident::new(&format!("{}UnknownEnumValue", ty_ident), ty_ident.span());
+    let error_doc = format!(
+        "An error indicating that an `i32` is not a valid value for `{}`.",
+        ty_ident
+    );
+
     let expanded = quote! {
         #impl_
 
@@ -282,17 +349,36 @@ fn try_enumeration(_attr: TokenStream, input: TokenStream) -> Result<TokenStream
             }
         }
 
-        impl ::std::convert::From<i32> for #ty {
+        impl ::std::convert::From<#ty> for i32 {
             #[inline]
-            fn from(value: i32) -> #ty {
-                #ty(value)
+            fn from(value: #ty) -> i32 {
+                value.0
             }
         }
 
-        impl ::std::convert::From<#ty> for i32 {
+        #[doc = #error_doc]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct #error_ident(i32);
+
+        impl ::std::fmt::Display for #error_ident {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                write!(f, "{} is not a valid value for enum {}", self.0, stringify!(#ty_ident))
+            }
+        }
+
+        impl ::std::error::Error for #error_ident {}
+
+        impl ::std::convert::TryFrom<i32> for #ty {
+            type Error = #error_ident;
+
             #[inline]
-            fn from(value: #ty) -> i32 {
-                value.0
+            fn try_from(value: i32) -> ::std::result::Result<#ty, #error_ident> {
+                let value = #ty(value);
+                if value.is_valid() {
+                    ::std::result::Result::Ok(value)
+                } else {
+                    ::std::result::Result::Err(#error_ident(value.0))
+                }
             }
         }
     };
@@ -302,7 +388,62 @@ fn try_enumeration(_attr: TokenStream, input: TokenStream) -> Result<TokenStream
 
 #[proc_macro_attribute]
 pub fn enumeration(attr: TokenStream, input: TokenStream) -> TokenStream {
-    try_enumeration(attr, input).unwrap()
+    try_enumeration(attr, input).unwrap_or_else(to_compile_error)
+}
+
+#[proc_macro_derive(Message, attributes(prost))]
+pub fn message(input: TokenStream) -> TokenStream {
+    try_message(input).unwrap_or_else(to_compile_error)
+}
+
+/// Converts a failed expansion into a `TokenStream` of `compile_error!`
+/// invocations, one per accumulated `syn::Error`, so that a single bad
+/// derive input can surface every mistake at once instead of aborting on
+/// the first one.
+fn to_compile_error(error: Error) -> TokenStream {
+    match error.downcast::<syn::Error>() {
+        Ok(error) => error.to_compile_error().into(),
+        Err(error) => syn::Error::new(Span::call_site(), error)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// Converts a PascalCase variant identifier into snake_case, e.g. `FooBar`
+/// becomes `foo_bar`. Used to derive `is_variant`-style method names from
+/// `Oneof` variant idents.
+///
+/// Runs of consecutive uppercase characters are treated as a single
+/// acronym rather than one word per character, so `HTTPResponse` becomes
+/// `http_response` and `GrpcURL` becomes `grpc_url`, matching the
+/// behavior of `heck::AsSnakeCase`.
+fn to_snake_case(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut snake = String::with_capacity(s.len());
+    for (i, &ch) in chars.iter().enumerate() {
+        if ch.is_uppercase() {
+            let prev_lower = i != 0 && chars[i - 1].is_lowercase();
+            let prev_upper = i != 0 && chars[i - 1].is_uppercase();
+            let next_lower = i + 1 < chars.len() && chars[i + 1].is_lowercase();
+            if i != 0 && (prev_lower || (prev_upper && next_lower)) {
+                snake.push('_');
+            }
+            snake.extend(ch.to_lowercase());
+        } else {
+            snake.push(ch);
+        }
+    }
+    snake
+}
+
+/// Returns whether any of `attrs` contains a bare `#[prost(<name>)]` flag.
+fn has_prost_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().flat_map(Attribute::parse_meta).any(|meta| match meta {
+        Meta::List(MetaList { path, nested, .. }) if path.is_ident("prost") => nested
+            .iter()
+            .any(|nested| matches!(nested, NestedMeta::Meta(Meta::Path(p)) if p.is_ident(name))),
+        _ => false,
+    })
 }
 
 fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
@@ -321,7 +462,8 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
     }
 
     // Map the variants into 'fields'.
-    let mut fields: Vec<(Ident, Field)> = Vec::new();
+    let ctxt = Ctxt::new();
+    let mut fields: Vec<(Ident, Field, bool)> = Vec::new();
     for Variant {
         attrs,
         ident: variant_ident,
@@ -337,39 +479,49 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
             }) => fields,
         };
         if variant_fields.len() != 1 {
-            bail!("Oneof enum variants must have a single field");
+            ctxt.error_spanned_by(&variant_ident, "Oneof enum variants must have a single field");
+            continue;
         }
-        match Field::new_oneof(attrs)? {
-            Some(field) => fields.push((variant_ident, field)),
-            None => bail!("invalid oneof variant: oneof variants may not be ignored"),
+        let no_from = has_prost_attr(&attrs, "no_from");
+        match Field::new(&ctxt, attrs, None) {
+            Some(field) => fields.push((variant_ident, field, no_from)),
+            None => ctxt.error_spanned_by(
+                &variant_ident,
+                "invalid oneof variant: oneof variants may not be ignored",
+            ),
         }
     }
 
     let mut tags = fields
         .iter()
-        .flat_map(|&(ref variant_ident, ref field)| -> Result<u32, Error> {
+        .flat_map(|&(ref variant_ident, ref field, _)| {
             if field.tags().len() > 1 {
-                bail!(
-                    "invalid oneof variant {}::{}: oneof variants may only have a single tag",
-                    ident,
-                    variant_ident
+                ctxt.error_spanned_by(
+                    variant_ident,
+                    format!(
+                        "invalid oneof variant {}::{}: oneof variants may only have a single tag",
+                        ident, variant_ident
+                    ),
                 );
+                return None;
             }
-            Ok(field.tags()[0])
+            Some(field.tags()[0])
         })
         .collect::<Vec<_>>();
     tags.sort();
     tags.dedup();
     if tags.len() != fields.len() {
-        panic!("invalid oneof {}: variants have duplicate tags", ident);
+        ctxt.error_spanned_by(&ident, format!("invalid oneof {}: variants have duplicate tags", ident));
     }
 
-    let encode = fields.iter().map(|&(ref variant_ident, ref field)| {
+    ctxt.check()?;
+
+    let encode = fields.iter().map(|&(ref variant_ident, ref field, _)| {
         let encode = field.encode(quote!(*value));
         quote!(#ident::#variant_ident(ref value) => { #encode })
     });
 
-    let merge = fields.iter().map(|&(ref variant_ident, ref field)| {
+    let merge = fields.iter().map(|&(ref variant_ident, ref field, _)| {
         let tag = field.tags()[0];
         let merge = field.merge(quote!(value));
         quote! {
@@ -388,21 +540,71 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         }
     });
 
-    let encoded_len = fields.iter().map(|&(ref variant_ident, ref field)| {
+    let encoded_len = fields.iter().map(|&(ref variant_ident, ref field, _)| {
         let encoded_len = field.encoded_len(quote!(*value));
         quote!(#ident::#variant_ident(ref value) => #encoded_len)
     });
 
-    let debug = fields.iter().map(|&(ref variant_ident, ref field)| {
-        let wrapper = field.debug(quote!(*value));
-        quote!(#ident::#variant_ident(ref value) => {
-            let wrapper = #wrapper;
-            f.debug_tuple(stringify!(#variant_ident))
-                .field(&wrapper)
-                .finish()
-        })
+    let debug = fields.iter().map(|&(ref variant_ident, ref field, _)| {
+        if field.skip_debug() {
+            // Mirror `try_message`, which omits `debug = "skip"` fields
+            // from the generated `Debug` impl entirely: the variant name
+            // is still shown, but its value is never formatted.
+            quote!(#ident::#variant_ident(..) => {
+                f.debug_tuple(stringify!(#variant_ident)).finish()
+            })
+        } else {
+            let wrapper = field.debug(quote!(*value));
+            quote!(#ident::#variant_ident(ref value) => {
+                let wrapper = #wrapper;
+                f.debug_tuple(stringify!(#variant_ident))
+                    .field(&wrapper)
+                    .finish()
+            })
+        }
+    });
+
+    let is_variant = fields.iter().map(|&(ref variant_ident, _, _)| {
+        let is_variant_ident = Ident::new(
+            &format!("is_{}", to_snake_case(&variant_ident.to_string())),
+            Span::call_site(),
+        );
+        let doc = format!("Returns `true` if this is a `{}` variant.", variant_ident);
+        quote! {
+            #[doc = #doc]
+            pub fn #is_variant_ident(&self) -> bool {
+                matches!(self, Self::#variant_ident(_))
+            }
+        }
     });
 
+    // Group variants by their inner Rust type so that a `From` impl is only
+    // generated when the conversion is unambiguous: if two variants wrap the
+    // same type, neither gets a `From` impl unless the others are excluded
+    // via `#[prost(no_from)]`.
+    let mut rust_type_counts = HashMap::new();
+    for &(_, ref field, no_from) in &fields {
+        if !no_from {
+            *rust_type_counts
+                .entry(field.rust_type().to_string())
+                .or_insert(0u32) += 1;
+        }
+    }
+    let from_impls = fields
+        .iter()
+        .filter(|&&(_, _, no_from)| !no_from)
+        .filter(|&&(_, ref field, _)| rust_type_counts[&field.rust_type().to_string()] == 1)
+        .map(|&(ref variant_ident, ref field, _)| {
+            let rust_type = field.rust_type();
+            quote! {
+                impl ::std::convert::From<#rust_type> for #ident {
+                    fn from(value: #rust_type) -> #ident {
+                        #ident::#variant_ident(value)
+                    }
+                }
+            }
+        });
+
     let expanded = quote! {
         impl #ident {
             pub fn encode<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
@@ -431,6 +633,8 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                     #(#encoded_len,)*
                 }
             }
+
+            #(#is_variant)*
         }
 
         impl ::std::fmt::Debug for #ident {
@@ -440,6 +644,8 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                 }
             }
         }
+
+        #(#from_impls)*
     };
 
     Ok(expanded.into())
@@ -447,5 +653,5 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
 
 #[proc_macro_derive(Oneof, attributes(prost))]
 pub fn oneof(input: TokenStream) -> TokenStream {
-    try_oneof(input).unwrap()
+    try_oneof(input).unwrap_or_else(to_compile_error)
 }
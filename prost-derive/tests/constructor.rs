@@ -0,0 +1,24 @@
+//! Exercises the `new(...)` constructor generated by `#[prost(constructor)]`,
+//! in particular that its argument order matches field declaration order
+//! rather than the tag-sorted order used for encoding.
+
+use prost_derive::Message;
+
+#[derive(Clone, PartialEq, Message)]
+#[prost(constructor)]
+struct Event {
+    #[prost(string, tag = "3")]
+    name: String,
+    #[prost(uint32, tag = "1")]
+    id: u32,
+    #[prost(bool, tag = "2")]
+    active: bool,
+}
+
+#[test]
+fn new_takes_arguments_in_declaration_order() {
+    let event = Event::new("checkout".to_string(), 42, true);
+    assert_eq!(event.name, "checkout");
+    assert_eq!(event.id, 42);
+    assert!(event.active);
+}
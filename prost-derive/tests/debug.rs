@@ -0,0 +1,62 @@
+//! Exercises the `#[prost(debug = "...")]` rendering added to the derived
+//! `Debug` impl, for both `Message` structs and `Oneof` enums.
+
+use prost_derive::{Message, Oneof};
+
+#[derive(Clone, PartialEq, Message)]
+struct Credentials {
+    #[prost(string, tag = "1")]
+    username: String,
+    #[prost(string, tag = "2", debug = "redact")]
+    password: String,
+    #[prost(string, tag = "3", debug = "skip")]
+    session_cookie: String,
+}
+
+#[test]
+fn redact_hides_the_value_but_keeps_the_field() {
+    let creds = Credentials {
+        username: "alice".to_string(),
+        password: "hunter2".to_string(),
+        session_cookie: "abc123".to_string(),
+    };
+    let rendered = format!("{:?}", creds);
+    assert!(rendered.contains("username: \"alice\""));
+    assert!(rendered.contains("<redacted>"));
+    assert!(!rendered.contains("hunter2"));
+}
+
+#[test]
+fn skip_omits_the_field_entirely() {
+    let creds = Credentials {
+        username: "alice".to_string(),
+        password: "hunter2".to_string(),
+        session_cookie: "abc123".to_string(),
+    };
+    let rendered = format!("{:?}", creds);
+    assert!(!rendered.contains("session_cookie"));
+    assert!(!rendered.contains("abc123"));
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+enum Secret {
+    #[prost(string, tag = "1")]
+    Public(String),
+    #[prost(string, tag = "2", debug = "skip")]
+    Private(String),
+}
+
+#[test]
+fn oneof_skip_omits_the_variants_value() {
+    let secret = Secret::Private("s3cr3t".to_string());
+    let rendered = format!("{:?}", secret);
+    assert_eq!(rendered, "Private");
+    assert!(!rendered.contains("s3cr3t"));
+}
+
+#[test]
+fn oneof_normal_variant_formats_as_usual() {
+    let secret = Secret::Public("hello".to_string());
+    let rendered = format!("{:?}", secret);
+    assert_eq!(rendered, "Public(\"hello\")");
+}
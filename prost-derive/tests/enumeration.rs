@@ -0,0 +1,29 @@
+//! Exercises the `TryFrom<i32>` impl generated by `#[prost::enumeration]`.
+
+use std::convert::TryFrom;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Color(i32);
+
+#[prost_derive::enumeration]
+impl Color {
+    const RED: Color = Color(0);
+    const GREEN: Color = Color(1);
+    const BLUE: Color = Color(2);
+}
+
+#[test]
+fn try_from_valid_value_succeeds() {
+    assert_eq!(Color::try_from(1).unwrap(), Color::GREEN);
+}
+
+#[test]
+fn try_from_invalid_value_returns_an_error() {
+    let error = Color::try_from(99).unwrap_err();
+    assert_eq!(error.to_string(), "99 is not a valid value for enum Color");
+}
+
+#[test]
+fn default_is_the_first_variant() {
+    assert_eq!(Color::default(), Color::RED);
+}